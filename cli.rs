@@ -0,0 +1,101 @@
+//! Headless CLI for the matcher, for server-side Takeout dumps and cron jobs where a
+//! display isn't available. Shares the matching engine in `lib.rs` with the GUI.
+
+use google_photos_metadata_matcher::watch::watch_folder;
+use google_photos_metadata_matcher::{match_metadata_core, MatchOptions, ProgressMessage};
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::sync::Arc;
+
+fn print_usage() {
+    eprintln!(
+        "Usage: google-photos-metadata-matcher-cli <folder> [--recursive] [--copy] [--output <dir>] [--delete-json] [--dry-run] [--watch]"
+    );
+}
+
+fn main() -> ExitCode {
+    let mut folder: Option<PathBuf> = None;
+    let mut options = MatchOptions::default();
+    let mut watch = false;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--recursive" => options.search_subdirs = true,
+            "--copy" => options.copy = true,
+            "--delete-json" => options.delete_json = true,
+            "--dry-run" => options.dry_run = true,
+            "--watch" => watch = true,
+            "--output" => {
+                i += 1;
+                match args.get(i) {
+                    Some(value) => options.output_dir = Some(PathBuf::from(value)),
+                    None => {
+                        eprintln!("--output requires a directory argument");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            other => folder = Some(PathBuf::from(other)),
+        }
+        i += 1;
+    }
+
+    let folder = match folder {
+        Some(folder) => folder,
+        None => {
+            print_usage();
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if options.copy && options.output_dir.is_none() {
+        eprintln!("--copy requires --output <dir>");
+        return ExitCode::FAILURE;
+    }
+
+    let (progress_sender, progress_receiver) = mpsc::channel();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+
+    if watch {
+        async_std::task::spawn(watch_folder(folder, options, stop_flag, progress_sender));
+    } else {
+        async_std::task::spawn(match_metadata_core(
+            vec![folder],
+            options,
+            stop_flag,
+            progress_sender,
+        ));
+    }
+
+    while let Ok(message) = progress_receiver.recv() {
+        match message {
+            ProgressMessage::Update(p) => {
+                if !p.current_file.is_empty() {
+                    println!(
+                        "[{}/{}] {}: {}",
+                        p.files_done, p.files_total, p.current_step, p.current_file
+                    );
+                }
+            }
+            ProgressMessage::Finished(report) => {
+                println!(
+                    "matched: {}, skipped: {}, failed: {}",
+                    report.matched, report.skipped, report.failed
+                );
+                for message in &report.messages {
+                    println!("{}", message);
+                }
+                if !watch {
+                    return ExitCode::SUCCESS;
+                }
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use little_exif::exif_tag::ExifTag;
+use little_exif::metadata::Metadata;
+use little_exif::rational::uR64;
+use std::path::Path;
+use std::time::SystemTime;
+
+// Extensions little_exif can write GPS/EXIF tags into. PNGs and videos are skipped and
+// reported through the error-collection path instead of failing the whole run.
+const EXIF_CAPABLE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "tif", "tiff"];
+
+/// GPS coordinates parsed from a Takeout JSON's `geoData` object.
+#[derive(Clone, Copy, Debug)]
+pub struct GeoData {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+impl GeoData {
+    /// Google fills in `geoData` with all-zero coordinates when a photo has no location;
+    /// treat that as "nothing to write" rather than stamping null island.
+    pub fn is_present(&self) -> bool {
+        self.latitude != 0.0 || self.longitude != 0.0
+    }
+}
+
+pub fn supports_exif(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| EXIF_CAPABLE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Writes `geo` into `path`'s EXIF GPS tags, optionally backfilling `DateTimeOriginal`
+/// from `taken_at` so the capture time survives re-import into other photo tools.
+pub fn write_gps(path: &Path, geo: &GeoData, taken_at: Option<SystemTime>) -> Result<(), String> {
+    let mut metadata = Metadata::new_from_path(path)
+        .map_err(|err| format!("Failed to read EXIF from {:?}: {}", path, err))?;
+
+    metadata.set_tag(ExifTag::GPSLatitudeRef(
+        if geo.latitude >= 0.0 { "N" } else { "S" }.to_string(),
+    ));
+    metadata.set_tag(ExifTag::GPSLatitude(decimal_to_dms(geo.latitude.abs())));
+    metadata.set_tag(ExifTag::GPSLongitudeRef(
+        if geo.longitude >= 0.0 { "E" } else { "W" }.to_string(),
+    ));
+    metadata.set_tag(ExifTag::GPSLongitude(decimal_to_dms(geo.longitude.abs())));
+    metadata.set_tag(ExifTag::GPSAltitudeRef(vec![if geo.altitude >= 0.0 {
+        0
+    } else {
+        1
+    }]));
+    metadata.set_tag(ExifTag::GPSAltitude(vec![uR64 {
+        nominator: (geo.altitude.abs() * 100.0).round() as u32,
+        denominator: 100,
+    }]));
+
+    if let Some(taken_at) = taken_at {
+        let formatted = DateTime::<Utc>::from(taken_at)
+            .format("%Y:%m:%d %H:%M:%S")
+            .to_string();
+        metadata.set_tag(ExifTag::DateTimeOriginal(formatted));
+    }
+
+    metadata
+        .write_to_file(path)
+        .map_err(|err| format!("Failed to write EXIF to {:?}: {}", path, err))
+}
+
+fn decimal_to_dms(value: f64) -> Vec<uR64> {
+    let degrees = value.trunc();
+    let minutes_full = (value - degrees) * 60.0;
+    let minutes = minutes_full.trunc();
+    let seconds = (minutes_full - minutes) * 60.0;
+
+    vec![
+        uR64 {
+            nominator: degrees as u32,
+            denominator: 1,
+        },
+        uR64 {
+            nominator: minutes as u32,
+            denominator: 1,
+        },
+        uR64 {
+            nominator: (seconds * 1000.0).round() as u32,
+            denominator: 1000,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_is_all_zero_dms() {
+        let dms = decimal_to_dms(0.0);
+
+        assert_eq!(dms[0].nominator, 0);
+        assert_eq!(dms[1].nominator, 0);
+        assert_eq!(dms[2].nominator, 0);
+    }
+
+    #[test]
+    fn whole_degree_has_no_minutes_or_seconds() {
+        let dms = decimal_to_dms(45.0);
+
+        assert_eq!((dms[0].nominator, dms[0].denominator), (45, 1));
+        assert_eq!((dms[1].nominator, dms[1].denominator), (0, 1));
+        assert_eq!((dms[2].nominator, dms[2].denominator), (0, 1000));
+    }
+
+    #[test]
+    fn fractional_degree_splits_into_minutes_and_seconds() {
+        // 40.7128 degrees -> 40 deg, 42 min, 46.08 sec
+        let dms = decimal_to_dms(40.7128);
+
+        assert_eq!((dms[0].nominator, dms[0].denominator), (40, 1));
+        assert_eq!((dms[1].nominator, dms[1].denominator), (42, 1));
+        assert_eq!((dms[2].nominator, dms[2].denominator), (46080, 1000));
+    }
+
+    #[test]
+    fn seconds_round_to_the_nearest_millisecond() {
+        // 1 degree + 1 minute + 59.9996 seconds should round up to 60.000 rather than
+        // truncate to 59.999.
+        let dms = decimal_to_dms(1.0 + 1.0 / 60.0 + 59.9996 / 3600.0);
+
+        assert_eq!((dms[2].nominator, dms[2].denominator), (60000, 1000));
+    }
+}
@@ -0,0 +1,612 @@
+//! Core Google Photos Takeout metadata matching logic.
+//!
+//! This is shared by the `eframe` GUI in `main.rs` and the headless CLI in `cli.rs` so
+//! the matching engine can run unattended (scripts, cron jobs, server-side Takeout
+//! dumps) without a display.
+
+pub mod geo;
+pub mod resolver;
+pub mod watch;
+
+use async_std::fs;
+use async_std::prelude::StreamExt;
+use geo::GeoData;
+use resolver::resolve_photo_paths;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+
+use filetime_creation::{set_file_ctime, set_file_mtime, FileTime};
+
+/// Options controlling a single matching run, set either from the GUI's checkboxes or
+/// the CLI's flags.
+#[derive(Clone, Default)]
+pub struct MatchOptions {
+    pub search_subdirs: bool,
+    pub copy: bool,
+    pub delete_json: bool,
+    pub dry_run: bool,
+    /// Destination for `copy`; matched photos are copied here (preserving each job's
+    /// subfolder structure) instead of being stamped in place.
+    pub output_dir: Option<PathBuf>,
+}
+
+// Progress payload streamed from the matching task to the UI, mirroring czkawka's
+// scan-progress messages so callers can show a filename and a running count instead of
+// just a bare percentage.
+#[derive(Clone, Default)]
+pub struct ProgressData {
+    pub files_total: usize,
+    pub files_done: usize,
+    pub current_step: String,
+    pub current_file: String,
+}
+
+impl ProgressData {
+    pub fn fraction(&self) -> f32 {
+        if self.files_total == 0 {
+            0.0
+        } else {
+            self.files_done as f32 / self.files_total as f32
+        }
+    }
+}
+
+// Final tally for a run, mirroring czkawka's `text_messages`: rather than aborting on the
+// first malformed JSON or read-only file, every per-file problem is collected here and
+// shown to the caller once the run finishes.
+#[derive(Clone, Default)]
+pub struct MatchReport {
+    pub matched: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub messages: Vec<String>,
+}
+
+// Outcome of trying to stamp a single photo with its metadata.
+pub(crate) enum MatchOutcome {
+    Matched,
+    Skipped(String),
+    Failed(String),
+}
+
+// Folds a single element's outcome into the running report, used by both a full rescan
+// and the incremental batches the watch mode processes.
+pub(crate) fn fold_outcome(report: &mut MatchReport, outcome: MatchOutcome) -> bool {
+    match outcome {
+        MatchOutcome::Matched => {
+            report.matched += 1;
+            true
+        }
+        MatchOutcome::Skipped(message) => {
+            report.skipped += 1;
+            report.messages.push(message);
+            false
+        }
+        MatchOutcome::Failed(message) => {
+            report.failed += 1;
+            report.messages.push(message);
+            false
+        }
+    }
+}
+
+// Messages streamed from the matching task to the caller: incremental progress updates,
+// followed by exactly one final report once the run (or cancellation) completes.
+pub enum ProgressMessage {
+    Update(ProgressData),
+    Finished(MatchReport),
+}
+
+/// Scans every folder in `paths` for Takeout JSONs, matches each one to its photo, and
+/// stamps the metadata onto it (or onto a copy under `options.output_dir`, if
+/// `options.copy` is set), streaming progress and a single final report over
+/// `progress_sender`. Jobs are scanned and processed in order but share one combined
+/// progress total, the same way a file manager queues several dropped sources into one
+/// job instead of running them as separate bars.
+/// Checked once per element so the GUI's "Cancel" button can stop a long run early via
+/// `stop_flag`.
+pub async fn match_metadata_core(
+    paths: Vec<PathBuf>,
+    options: MatchOptions,
+    stop_flag: Arc<AtomicBool>,
+    progress_sender: mpsc::Sender<ProgressMessage>,
+) {
+    println!("copy photos: {}", options.copy);
+    println!("subdirs: {}", options.search_subdirs);
+    println!("dry run: {}", options.dry_run);
+    println!("paths: {:?}", paths);
+
+    progress_sender
+        .send(ProgressMessage::Update(ProgressData {
+            files_total: 0,
+            files_done: 0,
+            current_step: "Scanning for JSON metadata".to_string(),
+            current_file: String::new(),
+        }))
+        .ok();
+
+    let mut elements = Vec::new();
+    let mut run_report = MatchReport::default();
+
+    for job_root in &paths {
+        let json_paths = if options.search_subdirs {
+            get_jsons_recursive(job_root).await
+        } else {
+            get_jsons(job_root).await
+        };
+
+        let (job_elements, job_report) = extract_metadata(json_paths, job_root.clone()).await;
+        elements.extend(job_elements);
+        run_report.failed += job_report.failed;
+        run_report.messages.extend(job_report.messages);
+    }
+
+    // open the files by the title inside of the json file and match the timestamps to the images
+    let total_elements = elements.len();
+    let mut current_element = 0;
+    let mut cancelled = false;
+
+    for element in elements {
+        if stop_flag.load(Ordering::SeqCst) {
+            run_report
+                .messages
+                .push("Cancelled by user, stopping early".to_string());
+            cancelled = true;
+            break;
+        }
+
+        let current_file = element.title.clone();
+        let json_path = element.source_dir.join(&element.json_file_name);
+
+        let (outcome, warnings) = if options.dry_run {
+            preview_match(&element, &options)
+        } else {
+            open_and_match(element, &options)
+        };
+        run_report.messages.extend(warnings);
+
+        let matched = fold_outcome(&mut run_report, outcome);
+
+        if matched && !options.dry_run && options.delete_json {
+            if let Err(err) = std::fs::remove_file(&json_path) {
+                run_report
+                    .messages
+                    .push(format!("Failed to delete {:?}: {}", json_path, err));
+            }
+        }
+
+        // progress
+        current_element += 1;
+        let progress_update = ProgressData {
+            files_total: total_elements,
+            files_done: current_element,
+            current_step: "Matching metadata".to_string(),
+            current_file,
+        };
+
+        match progress_sender.send(ProgressMessage::Update(progress_update.clone())) {
+            Ok(_) => println!(
+                "Sent progress: {}/{}",
+                progress_update.files_done, progress_update.files_total
+            ),
+            Err(err) => println!("Error sending progress: {}", err),
+        }
+    }
+
+    // A cancelled run should report its true (partial) progress rather than claiming the
+    // full total was reached, so the GUI doesn't show a 100% bar for a run that stopped
+    // early - the "Cancelled by user" message above is otherwise the only place that's
+    // reflected.
+    let (files_done, current_step) = if cancelled {
+        (current_element, "Cancelled".to_string())
+    } else {
+        (total_elements.max(1), "Done".to_string())
+    };
+
+    progress_sender
+        .send(ProgressMessage::Update(ProgressData {
+            files_total: total_elements.max(1),
+            files_done,
+            current_step,
+            current_file: String::new(),
+        }))
+        .ok();
+
+    progress_sender
+        .send(ProgressMessage::Finished(run_report))
+        .ok();
+}
+
+async fn get_jsons(path: &PathBuf) -> Vec<async_std::path::PathBuf> {
+    let mut json_paths = Vec::new();
+
+    if let Ok(mut entries) = fs::read_dir(&path).await {
+        while let Some(entry) = entries.next().await {
+            if let Ok(entry) = entry {
+                let file_path = entry.path();
+                if let Some(extension) = file_path.extension() {
+                    if extension == "json" {
+                        json_paths.push(file_path);
+                    }
+                }
+            }
+        }
+    } else {
+        println!("Failed to read directory: {:?}", path);
+    }
+
+    json_paths
+}
+
+// Walks `path` and every subdirectory below it, collecting `.json` files along the way.
+// Uses an explicit work queue (rather than recursive async fns, which Rust can't size) so
+// arbitrarily deep Takeout exports - split across dozens of album subfolders - are covered.
+async fn get_jsons_recursive(path: &PathBuf) -> Vec<async_std::path::PathBuf> {
+    let mut json_paths = Vec::new();
+    let mut dir_queue: Vec<async_std::path::PathBuf> = vec![async_std::path::PathBuf::from(path.clone())];
+
+    while let Some(dir) = dir_queue.pop() {
+        if let Ok(mut entries) = fs::read_dir(&dir).await {
+            while let Some(entry) = entries.next().await {
+                if let Ok(entry) = entry {
+                    let file_path = entry.path();
+
+                    match fs::metadata(&file_path).await {
+                        Ok(meta) if meta.is_dir() => dir_queue.push(file_path),
+                        Ok(_) => {
+                            if let Some(extension) = file_path.extension() {
+                                if extension == "json" {
+                                    json_paths.push(file_path);
+                                }
+                            }
+                        }
+                        Err(err) => println!("Failed to stat {:?}: {}", file_path, err),
+                    }
+                }
+            }
+        } else {
+            println!("Failed to read directory: {:?}", dir);
+        }
+    }
+
+    json_paths
+}
+
+pub(crate) struct GPhotosMetadata {
+    pub(crate) title: String,
+    phototaken_timestamp: i64,
+    // the directory the JSON itself was found in, so the photo is looked up next to it
+    // rather than relative to the top-level selected folder
+    pub(crate) source_dir: PathBuf,
+    // the JSON's own filename, needed to spot a duplicate counter Google moved off `title`
+    pub(crate) json_file_name: String,
+    // the folder this job was scanned from, so a copy can be placed at the same path
+    // relative to the output folder, preserving the source's subfolder structure
+    pub(crate) job_root: PathBuf,
+    geo_data: Option<GeoData>,
+}
+
+// Parses every JSON into a `GPhotosMetadata`. A single malformed file is recorded in the
+// returned report rather than aborting the whole batch.
+pub(crate) async fn extract_metadata(
+    json_paths: Vec<async_std::path::PathBuf>,
+    job_root: PathBuf,
+) -> (Vec<GPhotosMetadata>, MatchReport) {
+    let mut all_files_metadata = Vec::new();
+    let mut report = MatchReport::default();
+
+    for json_path in json_paths {
+        match extract_one(&json_path, &job_root).await {
+            Ok(metadata) => all_files_metadata.push(metadata),
+            Err(message) => {
+                report.failed += 1;
+                report.messages.push(message);
+            }
+        }
+    }
+
+    (all_files_metadata, report)
+}
+
+async fn extract_one(
+    json_path: &async_std::path::PathBuf,
+    job_root: &Path,
+) -> Result<GPhotosMetadata, String> {
+    let file_content = async_std::fs::read_to_string(json_path)
+        .await
+        .map_err(|err| format!("Failed to read JSON file {:?}: {}", json_path, err))?;
+
+    let json_value = serde_json::from_str::<Value>(&file_content)
+        .map_err(|err| format!("Failed to parse JSON file {:?}: {}", json_path, err))?;
+
+    let title = json_value
+        .get("title")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| format!("JSON file {:?} does not contain a 'title' property", json_path))?
+        .to_string();
+
+    let cr_time = json_value
+        .get("photoTakenTime")
+        .and_then(|t| t.get("timestamp"))
+        .and_then(|t| t.as_str())
+        .and_then(|t| t.parse::<i64>().ok())
+        .ok_or_else(|| {
+            format!(
+                "JSON file {:?} does not contain a valid 'photoTakenTime.timestamp' property",
+                json_path
+            )
+        })?;
+
+    let source_dir = json_path
+        .parent()
+        .map(|p| PathBuf::from(p.as_os_str()))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let json_file_name = json_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let geo_data = json_value.get("geoData").and_then(|g| {
+        let latitude = g.get("latitude")?.as_f64()?;
+        let longitude = g.get("longitude")?.as_f64()?;
+        let altitude = g.get("altitude").and_then(|a| a.as_f64()).unwrap_or(0.0);
+        let geo_data = GeoData {
+            latitude,
+            longitude,
+            altitude,
+        };
+        geo_data.is_present().then_some(geo_data)
+    });
+
+    Ok(GPhotosMetadata {
+        title,
+        phototaken_timestamp: cr_time,
+        source_dir,
+        json_file_name,
+        job_root: job_root.to_path_buf(),
+        geo_data,
+    })
+}
+
+// Resolves the on-disk match(es) for `el` and reports what would happen, without
+// touching the filesystem. Backs `MatchOptions::dry_run`.
+pub(crate) fn preview_match(el: &GPhotosMetadata, options: &MatchOptions) -> (MatchOutcome, Vec<String>) {
+    let file_paths = resolve_photo_paths(&el.source_dir, &el.json_file_name, &el.title);
+
+    if file_paths.is_empty() {
+        return (
+            MatchOutcome::Skipped(format!(
+                "No on-disk match for {:?} in {:?}, skipping",
+                el.title, el.source_dir
+            )),
+            Vec::new(),
+        );
+    }
+
+    let mut warnings: Vec<String> = file_paths
+        .iter()
+        .filter(|path| el.geo_data.is_some() && !geo::supports_exif(path))
+        .map(|path| format!("{:?} cannot hold EXIF data, skipping GPS tags", path))
+        .collect();
+
+    if options.copy {
+        match options.output_dir.as_ref() {
+            Some(output_dir) => {
+                for file_path in &file_paths {
+                    let relative = file_path
+                        .strip_prefix(&el.job_root)
+                        .ok()
+                        .or_else(|| file_path.file_name().map(Path::new))
+                        .unwrap_or(file_path);
+                    warnings.push(format!(
+                        "Would copy {:?} to {:?}",
+                        file_path,
+                        output_dir.join(relative)
+                    ));
+                }
+            }
+            None => warnings.push("Copy enabled but no output folder selected".to_string()),
+        }
+    }
+
+    (MatchOutcome::Matched, warnings)
+}
+
+// Returns the outcome for the whole element plus any non-fatal warnings gathered along
+// the way (e.g. a format that can't hold EXIF), which are folded into the run's report
+// regardless of whether the overall outcome is a match.
+pub(crate) fn open_and_match(
+    el: GPhotosMetadata,
+    options: &MatchOptions,
+) -> (MatchOutcome, Vec<String>) {
+    println!("{:?}", el.title);
+    println!("{:?}", el.phototaken_timestamp);
+
+    let mut warnings = Vec::new();
+
+    // Photo taken time
+    let phototaken_time = SystemTime::UNIX_EPOCH + Duration::new(el.phototaken_timestamp as u64, 0);
+    let phototaken_filetime = FileTime::from_system_time(phototaken_time);
+
+    let file_paths = resolve_photo_paths(&el.source_dir, &el.json_file_name, &el.title);
+
+    if file_paths.is_empty() {
+        println!("No file found for {:?}, skipping...", el.title);
+        return (
+            MatchOutcome::Skipped(format!(
+                "No on-disk match for {:?} in {:?}, skipping",
+                el.title, el.source_dir
+            )),
+            warnings,
+        );
+    }
+
+    for file_path in &file_paths {
+        let target_path = if options.copy {
+            match copy_to_output(file_path, &el.job_root, options) {
+                Ok(path) => path,
+                Err(err) => return (MatchOutcome::Failed(err), warnings),
+            }
+        } else {
+            file_path.clone()
+        };
+
+        if let Err(err) = set_file_ctime(&target_path, phototaken_filetime) {
+            return (
+                MatchOutcome::Failed(format!(
+                    "Failed to set creation time on {:?}: {}",
+                    target_path, err
+                )),
+                warnings,
+            );
+        }
+
+        if let Err(err) = set_file_mtime(&target_path, phototaken_filetime) {
+            return (
+                MatchOutcome::Failed(format!(
+                    "Failed to set modification time on {:?}: {}",
+                    target_path, err
+                )),
+                warnings,
+            );
+        }
+
+        if let Some(geo_data) = el.geo_data.as_ref() {
+            if !geo::supports_exif(&target_path) {
+                warnings.push(format!(
+                    "{:?} cannot hold EXIF data, skipping GPS tags",
+                    target_path
+                ));
+            } else if let Err(err) = geo::write_gps(&target_path, geo_data, Some(phototaken_time)) {
+                warnings.push(err);
+            }
+        }
+    }
+
+    (MatchOutcome::Matched, warnings)
+}
+
+// Copies `file_path` into `options.output_dir`, preserving its path relative to the job's
+// root folder so a multi-folder batch keeps each source's subfolder layout in the output.
+// A file outside the job root is placed directly in the output folder under its file name.
+// Returns the path to stamp metadata onto, leaving the original untouched.
+fn copy_to_output(
+    file_path: &Path,
+    job_root: &Path,
+    options: &MatchOptions,
+) -> Result<PathBuf, String> {
+    let output_dir = options
+        .output_dir
+        .as_ref()
+        .ok_or_else(|| "Copy enabled but no output folder selected".to_string())?;
+
+    let relative = file_path
+        .strip_prefix(job_root)
+        .ok()
+        .or_else(|| file_path.file_name().map(Path::new))
+        .unwrap_or(file_path);
+    let dest_path = output_dir.join(relative);
+
+    if let Some(parent) = dest_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {:?}: {}", parent, err))?;
+    }
+
+    std::fs::copy(file_path, &dest_path)
+        .map_err(|err| format!("Failed to copy {:?} to {:?}: {}", file_path, dest_path, err))?;
+
+    Ok(dest_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A throwaway directory under the OS temp dir, torn down on drop, so each case gets
+    // its own isolated tree to copy into/out of.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(label: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "gphotos-matcher-lib-test-{label}-{}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn copy_preserves_relative_subpath_under_job_root() {
+        let job_root = TestDir::new("copy-job-root");
+        let output = TestDir::new("copy-output");
+        fs::create_dir_all(job_root.path("album")).unwrap();
+        let source = job_root.path("album/IMG_1234.jpg");
+        fs::write(&source, b"photo").unwrap();
+
+        let options = MatchOptions {
+            output_dir: Some(output.0.clone()),
+            ..MatchOptions::default()
+        };
+
+        let dest = copy_to_output(&source, &job_root.0, &options).unwrap();
+
+        assert_eq!(dest, output.path("album/IMG_1234.jpg"));
+        assert_eq!(fs::read(&dest).unwrap(), b"photo");
+    }
+
+    #[test]
+    fn copy_falls_back_to_file_name_when_not_under_job_root() {
+        // `strip_prefix` fails here since `source` isn't under `job_root`, so `relative`
+        // falls back to the bare file name and the copy lands directly in `output_dir`
+        // instead of overwriting the source.
+        let job_root = TestDir::new("copy-unrelated-root");
+        let elsewhere = TestDir::new("copy-unrelated-source");
+        let output = TestDir::new("copy-unrelated-output");
+        let source = elsewhere.path("IMG_1234.jpg");
+        fs::write(&source, b"photo").unwrap();
+
+        let options = MatchOptions {
+            output_dir: Some(output.0.clone()),
+            ..MatchOptions::default()
+        };
+
+        let dest = copy_to_output(&source, &job_root.0, &options).unwrap();
+
+        assert_eq!(dest, output.path("IMG_1234.jpg"));
+        assert_eq!(fs::read(&dest).unwrap(), b"photo");
+    }
+
+    #[test]
+    fn copy_without_output_dir_is_an_error() {
+        let job_root = TestDir::new("copy-missing-output-root");
+        let source = job_root.path("IMG_1234.jpg");
+        fs::write(&source, b"photo").unwrap();
+
+        let options = MatchOptions::default();
+
+        let err = copy_to_output(&source, &job_root.0, &options).unwrap_err();
+
+        assert_eq!(err, "Copy enabled but no output folder selected");
+    }
+}
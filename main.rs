@@ -1,23 +1,14 @@
-use async_std::fs;
-use async_std::prelude::StreamExt;
 use eframe::{egui, NativeOptions};
 use egui::{ProgressBar, Ui};
-use filetime_creation::{set_file_ctime, set_file_mtime, FileTime};
+use google_photos_metadata_matcher::watch::watch_folder;
+use google_photos_metadata_matcher::{
+    match_metadata_core, MatchOptions, MatchReport, ProgressData, ProgressMessage,
+};
 use rfd::FileDialog;
-use serde_json::Value;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime};
-use std::vec::Vec;
-
-// todo
-// error window
-// show skipped files count
-// show matched files count
-// geo data
-// subdirs
-// optionally delete jsons
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
@@ -34,22 +25,34 @@ fn main() -> Result<(), eframe::Error> {
 
 struct Matcher {
     folder_path: String,
-    selected_folder: Option<PathBuf>,
+    selected_folders: Vec<PathBuf>,
+    output_folder: Option<PathBuf>,
     should_copy: bool,
     should_go_over_subdirs: bool,
-    progress: Arc<Mutex<f32>>,
+    should_delete_json: bool,
+    should_watch: bool,
+    progress: Arc<Mutex<ProgressData>>,
+    stop_flag: Arc<AtomicBool>,
     working_message: String,
+    report: Arc<Mutex<Option<MatchReport>>>,
+    show_report: bool,
 }
 
 impl Default for Matcher {
     fn default() -> Self {
         Self {
             folder_path: String::new(),
-            selected_folder: None,
+            selected_folders: Vec::new(),
+            output_folder: None,
             should_copy: false,
             should_go_over_subdirs: false,
-            progress: Arc::new(Mutex::new(0.0)),
+            should_delete_json: false,
+            should_watch: false,
+            progress: Arc::new(Mutex::new(ProgressData::default())),
+            stop_flag: Arc::new(AtomicBool::new(false)),
             working_message: "".to_string(),
+            report: Arc::new(Mutex::new(None)),
+            show_report: false,
         }
     }
 }
@@ -58,252 +61,267 @@ impl eframe::App for Matcher {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui: &mut Ui| {
             ui.horizontal(|ui: &mut Ui| {
-                // Select folder by entering path
+                // Add a folder by entering its path
                 ui.label("Folder Path:");
                 ui.add(egui::TextEdit::singleline(&mut self.folder_path).desired_width(200.0));
-                if ui.button("Select").clicked() {
+                if ui.button("Add").clicked() {
                     if let Ok(path) = std::fs::canonicalize(&self.folder_path) {
-                        self.selected_folder = Some(path);
-                    } else {
-                        self.selected_folder = None;
+                        self.selected_folders.push(path);
                     }
                 }
             });
 
-            // Select folder button by window dialog
-            if ui.button("Select Folder").clicked() {
-                if let Some(folder) = FileDialog::new()
-                    .set_directory(&std::env::current_dir().unwrap())
-                    .pick_folder()
-                {
-                    self.selected_folder = Some(folder);
+            ui.horizontal(|ui: &mut Ui| {
+                // Add one or more folders by window dialog
+                if ui.button("Add Folders").clicked() {
+                    if let Some(folders) = FileDialog::new()
+                        .set_directory(&std::env::current_dir().unwrap())
+                        .pick_folders()
+                    {
+                        self.selected_folders.extend(folders);
+                    }
                 }
-            }
+                if ui.button("Clear Folders").clicked() {
+                    self.selected_folders.clear();
+                }
+            });
 
-            // Show selected folder in ui
-            if let Some(folder) = self.selected_folder.as_ref().and_then(|p| p.to_str()) {
-                ui.label(format!("Selected folder: {}", folder));
+            // Show selected folders in ui
+            if self.selected_folders.is_empty() {
+                ui.label("No folders selected");
             } else {
-                ui.label("No folder selected");
-            }
-
-            // Those are not implemented
-            // ui.checkbox(&mut self.should_copy, "Copy photos to new folder");
-            // ui.checkbox(&mut self.should_go_over_subdirs, "Go over subdirectories");
-            // ui.checkbox(, "Delete json files");
-
-            // Match metadata button
-            if ui.button("Match metadata").clicked() {
-                if self.selected_folder == None {
-                    return;
+                for folder in &self.selected_folders {
+                    ui.label(format!("Selected folder: {}", folder.display()));
                 }
+            }
 
-                self.working_message = "Working...".to_string();
+            ui.checkbox(&mut self.should_copy, "Copy photos to new folder");
+            ui.checkbox(&mut self.should_go_over_subdirs, "Go over subdirectories");
+            ui.checkbox(&mut self.should_delete_json, "Delete json files");
+
+            if self.should_copy {
+                ui.horizontal(|ui: &mut Ui| {
+                    if ui.button("Select Output Folder").clicked() {
+                        if let Some(folder) = FileDialog::new()
+                            .set_directory(&std::env::current_dir().unwrap())
+                            .pick_folder()
+                        {
+                            self.output_folder = Some(folder);
+                        }
+                    }
+                    if let Some(folder) = self.output_folder.as_ref().and_then(|p| p.to_str()) {
+                        ui.label(format!("Output folder: {}", folder));
+                    } else {
+                        ui.label("No output folder selected");
+                    }
+                });
+            }
 
-                let matcher = Matcher {
-                    folder_path: self.folder_path.clone(),
-                    selected_folder: self.selected_folder.clone(),
-                    should_copy: self.should_copy,
-                    should_go_over_subdirs: self.should_go_over_subdirs,
-                    progress: self.progress.clone(),
-                    working_message: "".to_string(),
-                };
+            ui.checkbox(
+                &mut self.should_watch,
+                "Watch folder (process new files as they arrive)",
+            );
 
-                let ctx_clone = ctx.clone();
+            ui.horizontal(|ui: &mut Ui| {
+                // Match metadata button
+                if ui.button("Match metadata").clicked() {
+                    if self.selected_folders.is_empty() {
+                        return;
+                    }
+                    if self.should_copy && self.output_folder.is_none() {
+                        return;
+                    }
 
-                async_std::task::spawn(async move {
-                    match matcher.selected_folder {
-                        Some(folder) => {
+                    self.working_message = "Working...".to_string();
+                    self.show_report = false;
+                    self.stop_flag.store(false, Ordering::SeqCst);
+                    *self.progress.lock().unwrap() = ProgressData::default();
+                    *self.report.lock().unwrap() = None;
+
+                    let matcher = Matcher {
+                        folder_path: self.folder_path.clone(),
+                        selected_folders: self.selected_folders.clone(),
+                        output_folder: self.output_folder.clone(),
+                        should_copy: self.should_copy,
+                        should_go_over_subdirs: self.should_go_over_subdirs,
+                        should_delete_json: self.should_delete_json,
+                        should_watch: self.should_watch,
+                        progress: self.progress.clone(),
+                        stop_flag: self.stop_flag.clone(),
+                        working_message: "".to_string(),
+                        report: self.report.clone(),
+                        show_report: false,
+                    };
+
+                    let ctx_clone = ctx.clone();
+
+                    async_std::task::spawn(async move {
+                        if matcher.should_watch {
+                            watch(
+                                matcher.selected_folders,
+                                matcher.should_go_over_subdirs,
+                                matcher.should_copy,
+                                matcher.output_folder,
+                                matcher.should_delete_json,
+                                matcher.progress,
+                                matcher.stop_flag,
+                                matcher.report,
+                                ctx_clone,
+                            )
+                            .await;
+                        } else {
                             match_metadata(
-                                folder,
+                                matcher.selected_folders,
                                 matcher.should_go_over_subdirs,
                                 matcher.should_copy,
+                                matcher.output_folder,
+                                matcher.should_delete_json,
                                 matcher.progress,
+                                matcher.stop_flag,
+                                matcher.report,
                                 ctx_clone,
                             )
                             .await;
                         }
-                        None => {
-                            println!("No folder selected");
-                        }
-                    }
-                });
-            }
+                    });
+                }
+
+                // Cancel button
+                if ui.button("Cancel").clicked() {
+                    self.stop_flag.store(true, Ordering::SeqCst);
+                }
+            });
 
             // Progress bar
-            let prog = self.progress.lock().unwrap();
-            if *prog > 0.0 && *prog < 1.0 {
-                ui.add(ProgressBar::new(*prog).show_percentage());
-            } else if *prog >= 1.0 {
+            let prog = self.progress.lock().unwrap().clone();
+            if prog.files_total > 0 && prog.files_done < prog.files_total {
+                ui.add(ProgressBar::new(prog.fraction()).show_percentage());
+                ui.label(format!(
+                    "{}: matched {} / {}",
+                    prog.current_step, prog.files_done, prog.files_total
+                ));
+                ui.label(&prog.current_file);
+            } else if prog.files_total > 0 && prog.files_done >= prog.files_total {
                 ui.label("Metadata processing complete");
                 self.working_message = "".to_string();
+                self.show_report = true;
             }
 
             ui.label(&self.working_message);
         });
+
+        if self.show_report {
+            if let Some(report) = self.report.lock().unwrap().clone() {
+                display_report(ctx, &report, &mut self.show_report);
+            }
+        }
     }
 }
 
+fn display_report(ctx: &egui::Context, report: &MatchReport, open: &mut bool) {
+    egui::Window::new("Results").open(open).show(ctx, |ui| {
+        ui.label(format!(
+            "matched: {}, skipped: {}, failed: {}",
+            report.matched, report.skipped, report.failed
+        ));
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for message in &report.messages {
+                ui.label(message);
+            }
+        });
+    });
+}
+
+// Thin GUI wrapper around the library's `match_metadata_core`: spawns the matching work
+// on its own task and pumps `ctx.request_repaint()` as progress comes in over the channel.
 async fn match_metadata(
-    path: PathBuf,
+    folders: Vec<PathBuf>,
     search_subdirs: bool,
     copy: bool,
-    progress: Arc<Mutex<f32>>,
+    output_dir: Option<PathBuf>,
+    delete_json: bool,
+    progress: Arc<Mutex<ProgressData>>,
+    stop_flag: Arc<AtomicBool>,
+    report: Arc<Mutex<Option<MatchReport>>>,
     ctx: egui::Context,
 ) {
-    println!("copy photos: {}", copy);
-    println!("subdirs: {}", search_subdirs);
-    println!("path: {:?}", path);
+    let options = MatchOptions {
+        search_subdirs,
+        copy,
+        delete_json,
+        dry_run: false,
+        output_dir,
+    };
 
     let (progress_sender, progress_receiver) = mpsc::channel();
 
-    // let ctx_clone = ctx.clone();
-
-    async_std::task::spawn(async move {
-        if search_subdirs {
-            unimplemented!();
-            // disappears immediately due to request_repaint() on progress_receiver.recv()
-            // display_error(
-            //     &ctx_clone,
-            //     "Searching subdirectories is currently unimplemented.",
-            // )
-            // .await;
-            // return;
-        } else {
-            // search for jsons
-            let json_paths = get_jsons(&path).await;
-            let metadata = extract_metadata(json_paths).await;
-
-            // open the files by the title inside of the json file and match the timestamps to the images
-            match metadata {
-                Ok(m) => {
-                    let total_elements = m.len();
-                    let mut current_element = 0;
-
-                    for element in m {
-                        open_and_match(element, &path);
-
-                        // progress
-                        current_element += 1;
-                        let progress = (current_element as f32) / (total_elements as f32);
-
-                        match progress_sender.send(progress) {
-                            Ok(_) => println!("Sent progress: {}", progress),
-                            Err(err) => println!("Error sending progress: {}", err),
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("Error: {}", e);
-                    // display_error(&ctx_clone, e.as_str()).await;
-                    return;
-                }
-            }
-
-            progress_sender.send(1.0).unwrap();
-        }
-    });
+    async_std::task::spawn(match_metadata_core(
+        folders,
+        options,
+        stop_flag,
+        progress_sender,
+    ));
 
-    while let Ok(p) = progress_receiver.recv() {
+    while let Ok(message) = progress_receiver.recv() {
         ctx.request_repaint();
-        *progress.lock().unwrap() = p;
-    }
-}
-
-async fn get_jsons(path: &PathBuf) -> Vec<async_std::path::PathBuf> {
-    let mut json_paths = Vec::new();
-
-    if let Ok(mut entries) = fs::read_dir(&path).await {
-        while let Some(entry) = entries.next().await {
-            if let Ok(entry) = entry {
-                let file_path = entry.path();
-                if let Some(extension) = file_path.extension() {
-                    if extension == "json" {
-                        json_paths.push(file_path);
-                    }
-                }
-            }
+        match message {
+            ProgressMessage::Update(p) => *progress.lock().unwrap() = p,
+            ProgressMessage::Finished(r) => *report.lock().unwrap() = Some(r),
         }
-    } else {
-        println!("Failed to read directory: {:?}", path);
     }
-
-    json_paths
 }
 
-struct GPhotosMetadata {
-    title: String,
-    phototaken_timestamp: i64,
-    // todo geo data
-}
-
-async fn extract_metadata(
-    json_paths: Vec<async_std::path::PathBuf>,
-) -> Result<Vec<GPhotosMetadata>, String> {
-    let mut all_files_metadata = Vec::new();
-
-    for json_path in json_paths {
-        let file_content = async_std::fs::read_to_string(&json_path)
-            .await
-            .map_err(|err| format!("Failed to read JSON file {:?}: {}", json_path, err))?;
-
-        let json_value = serde_json::from_str::<Value>(&file_content)
-            .map_err(|err| format!("Failed to parse JSON file {:?}: {}", json_path, err))?;
-
-        let title = if let Some(t) = json_value.get("title") {
-            t.as_str().unwrap().to_string()
-        } else {
-            return Err(format!(
-                "JSON file {:?} does not contain 'title' property",
-                json_path
-            ));
-        };
-
-        let cr_time = if let Some(creation_time) = json_value.get("photoTakenTime") {
-            let timestamp = creation_time["timestamp"].as_str().unwrap();
-            timestamp.parse::<i64>().unwrap()
-        } else {
-            return Err(format!(
-                "JSON file {:?} does not contain 'photoTakenTime' property",
-                json_path
-            ));
-        };
+// Thin GUI wrapper around the library's `watch_folder`: spawns one watcher per selected
+// folder (they all run until the "Cancel" button sets `stop_flag`) and merges each
+// folder's final report into one, rather than silently watching only the first folder.
+async fn watch(
+    paths: Vec<PathBuf>,
+    search_subdirs: bool,
+    copy: bool,
+    output_dir: Option<PathBuf>,
+    delete_json: bool,
+    progress: Arc<Mutex<ProgressData>>,
+    stop_flag: Arc<AtomicBool>,
+    report: Arc<Mutex<Option<MatchReport>>>,
+    ctx: egui::Context,
+) {
+    let options = MatchOptions {
+        search_subdirs,
+        copy,
+        delete_json,
+        dry_run: false,
+        output_dir,
+    };
 
-        let metadata = GPhotosMetadata {
-            title: title,
-            phototaken_timestamp: cr_time,
-        };
+    let (progress_sender, progress_receiver) = mpsc::channel();
 
-        all_files_metadata.push(metadata);
+    for path in paths {
+        async_std::task::spawn(watch_folder(
+            path,
+            options.clone(),
+            stop_flag.clone(),
+            progress_sender.clone(),
+        ));
     }
+    drop(progress_sender);
 
-    return Ok(all_files_metadata);
-}
-
-// async fn display_error(ctx: &egui::Context, message: &str) {
-//     egui::Window::new("Error").show(ctx, |ui| {
-//         ui.add(egui::Label::new(message));
-//     });
-// }
-
-fn open_and_match(el: GPhotosMetadata, path: &PathBuf) {
-    println!("{:?}", el.title);
-    println!("{:?}", el.phototaken_timestamp);
-
-    // Photo taken time
-    let phototaken_time = SystemTime::UNIX_EPOCH + Duration::new(el.phototaken_timestamp as u64, 0);
-    FileTime::from_system_time(phototaken_time);
-    let phototaken_filetime = FileTime::from_system_time(phototaken_time);
-
-    // todo geo data
-
-    let file_path = path.join(&el.title);
-
-    if !file_path.exists() {
-        println!("File {:?} does not exist, skipping...", file_path);
-        return;
+    while let Ok(message) = progress_receiver.recv() {
+        ctx.request_repaint();
+        match message {
+            ProgressMessage::Update(p) => *progress.lock().unwrap() = p,
+            ProgressMessage::Finished(r) => {
+                let mut report = report.lock().unwrap();
+                *report = Some(match report.take() {
+                    Some(mut existing) => {
+                        existing.matched += r.matched;
+                        existing.skipped += r.skipped;
+                        existing.failed += r.failed;
+                        existing.messages.extend(r.messages);
+                        existing
+                    }
+                    None => r,
+                });
+            }
+        }
     }
-
-    set_file_ctime(&file_path, phototaken_filetime).expect("Failed to set creation file time");
-    set_file_mtime(&file_path, phototaken_filetime).expect("Failed to set modification file time");
 }
@@ -0,0 +1,288 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+// Google truncates long filenames to this length when writing the JSON `title` field.
+const TAKEOUT_TITLE_MAX_LEN: usize = 51;
+
+// Edited copies Google Photos produces without ever writing a JSON of their own.
+const EDITED_SUFFIXES: [&str; 2] = ["-edited", "-bearbeitet"];
+
+// Newer Takeout exports write JSONs as `<title>.supplemental-metadata.json` rather than
+// `<title>.json`. When a duplicate counter has to be squeezed in too, it lands either
+// before or after this infix, e.g. `IMG_1234.jpg(1).supplemental-metadata.json` or
+// `IMG_1234.jpg.supplemental-metadata(1).json`.
+const SUPPLEMENTAL_METADATA_INFIX: &str = ".supplemental-metadata";
+
+/// Resolves the on-disk photo path(s) that a Takeout JSON entry's `title` refers to.
+///
+/// Real Takeout exports rarely match `title` to a file verbatim: long names get
+/// truncated, duplicate counters get moved outside the extension, the JSON itself may be
+/// named with a `.supplemental-metadata` infix, and edited copies have no metadata file
+/// of their own. This tries a ranked series of candidates - migrated `(n)` counter, exact
+/// title, truncated-prefix fuzzy match - against `dir`'s listing, then appends any
+/// `-edited`/`-bearbeitet` sibling of whatever it found so the same timestamp can be
+/// propagated onto it too.
+pub fn resolve_photo_paths(dir: &Path, json_file_name: &str, title: &str) -> Vec<PathBuf> {
+    let mut resolved = Vec::new();
+    let json_file_name = strip_supplemental_metadata_infix(json_file_name);
+
+    // The migrated counter comes from the JSON's own filename, so it's unambiguous about
+    // which on-disk duplicate this JSON belongs to - check it first so it isn't shadowed
+    // by a base photo of the same title sitting right next to it. If the predicted
+    // counter name doesn't actually exist, still fall back to the bare title before
+    // giving up on an exact match entirely.
+    if let Some(migrated) = migrated_counter_name(&json_file_name, title) {
+        let migrated = dir.join(migrated);
+        if migrated.exists() {
+            resolved.push(migrated);
+        }
+    }
+
+    if resolved.is_empty() {
+        let exact = dir.join(title);
+        if exact.exists() {
+            resolved.push(exact);
+        }
+    }
+
+    if resolved.is_empty() {
+        if let Some(fuzzy) = fuzzy_truncated_match(dir, title) {
+            resolved.push(fuzzy);
+        }
+    }
+
+    let siblings: Vec<PathBuf> = resolved
+        .iter()
+        .flat_map(|path| edited_siblings(dir, path))
+        .collect();
+    resolved.extend(siblings);
+
+    resolved
+}
+
+/// Google sometimes moves a duplicate's `(n)` counter out of the JSON `title` and onto the
+/// JSON filename itself, e.g. title `IMG_1234.jpg` paired with filename
+/// `IMG_1234.jpg(1).json`. When that happens the photo on disk keeps the counter next to
+/// its own extension instead: `IMG_1234(1).jpg`.
+fn migrated_counter_name(json_file_name: &str, title: &str) -> Option<String> {
+    let counter = json_file_name.strip_prefix(title)?.strip_suffix(".json")?;
+
+    if counter.starts_with('(') && counter.ends_with(')') {
+        let (stem, ext) = split_stem_ext(title);
+        Some(match ext {
+            Some(ext) => format!("{stem}{counter}.{ext}"),
+            None => format!("{stem}{counter}"),
+        })
+    } else {
+        None
+    }
+}
+
+/// Drops a `.supplemental-metadata` infix from a JSON filename, relocating any `(n)`
+/// counter found next to it back next to `.json` - e.g. both
+/// `IMG_1234.jpg(1).supplemental-metadata.json` and
+/// `IMG_1234.jpg.supplemental-metadata(1).json` become `IMG_1234.jpg(1).json`, so the
+/// rest of the resolver can treat the name as if the infix were never there.
+fn strip_supplemental_metadata_infix(json_file_name: &str) -> Cow<'_, str> {
+    match json_file_name.find(SUPPLEMENTAL_METADATA_INFIX) {
+        Some(index) => {
+            let before = &json_file_name[..index];
+            let after = &json_file_name[index + SUPPLEMENTAL_METADATA_INFIX.len()..];
+            Cow::Owned(format!("{before}{after}"))
+        }
+        None => Cow::Borrowed(json_file_name),
+    }
+}
+
+fn split_stem_ext(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => (stem, Some(ext)),
+        None => (name, None),
+    }
+}
+
+/// Titles longer than `TAKEOUT_TITLE_MAX_LEN` were truncated by Google before being written
+/// to the JSON. Find the (hopefully unique) file in `dir` whose name starts with that
+/// truncated prefix.
+fn fuzzy_truncated_match(dir: &Path, title: &str) -> Option<PathBuf> {
+    if title.chars().count() < TAKEOUT_TITLE_MAX_LEN {
+        return None;
+    }
+
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(OsStr::to_str)
+                .map(|name| name.starts_with(title))
+                .unwrap_or(false)
+        })
+}
+
+/// Returns any `-edited`/`-bearbeitet` sibling of `path`, since Google never writes a JSON
+/// for the edited copy and it should still get the original photo's timestamp.
+fn edited_siblings(dir: &Path, path: &Path) -> Vec<PathBuf> {
+    let stem = match path.file_stem().and_then(OsStr::to_str) {
+        Some(stem) => stem,
+        None => return Vec::new(),
+    };
+    let ext = path.extension().and_then(OsStr::to_str);
+
+    EDITED_SUFFIXES
+        .iter()
+        .filter_map(|suffix| {
+            let name = match ext {
+                Some(ext) => format!("{stem}{suffix}.{ext}"),
+                None => format!("{stem}{suffix}"),
+            };
+            let candidate = dir.join(name);
+            candidate.exists().then_some(candidate)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A throwaway directory under the OS temp dir, torn down on drop, so each case gets
+    // its own isolated listing to resolve against.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(label: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "gphotos-matcher-resolver-test-{label}-{}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+
+        fn touch(&self, name: &str) -> &Self {
+            fs::write(self.0.join(name), b"").unwrap();
+            self
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn exact_title_match() {
+        let dir = TestDir::new("exact");
+        dir.touch("IMG_1234.jpg");
+
+        let resolved = resolve_photo_paths(&dir.0, "IMG_1234.jpg.json", "IMG_1234.jpg");
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234.jpg")]);
+    }
+
+    #[test]
+    fn migrated_counter_is_preferred_over_bare_exact_title() {
+        let dir = TestDir::new("migrated");
+        dir.touch("IMG_1234.jpg").touch("IMG_1234(1).jpg");
+
+        let resolved = resolve_photo_paths(&dir.0, "IMG_1234.jpg(1).json", "IMG_1234.jpg");
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234(1).jpg")]);
+    }
+
+    #[test]
+    fn migrated_counter_falls_back_to_exact_title_when_predicted_file_missing() {
+        let dir = TestDir::new("migrated-missing");
+        dir.touch("IMG_1234.jpg");
+
+        let resolved = resolve_photo_paths(&dir.0, "IMG_1234.jpg(1).json", "IMG_1234.jpg");
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234.jpg")]);
+    }
+
+    #[test]
+    fn fuzzy_truncated_prefix_match() {
+        let dir = TestDir::new("fuzzy");
+        let full_name = format!("{}-full-name.jpg", "a".repeat(60));
+        dir.touch(&full_name);
+        let truncated_title: String = full_name.chars().take(TAKEOUT_TITLE_MAX_LEN).collect();
+
+        let resolved = resolve_photo_paths(&dir.0, "whatever.json", &truncated_title);
+
+        assert_eq!(resolved, vec![dir.path(&full_name)]);
+    }
+
+    #[test]
+    fn supplemental_metadata_infix_with_counter_after() {
+        let dir = TestDir::new("suppl-after");
+        dir.touch("IMG_1234.jpg").touch("IMG_1234(1).jpg");
+
+        let resolved = resolve_photo_paths(
+            &dir.0,
+            "IMG_1234.jpg.supplemental-metadata(1).json",
+            "IMG_1234.jpg",
+        );
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234(1).jpg")]);
+    }
+
+    #[test]
+    fn supplemental_metadata_infix_with_counter_before() {
+        let dir = TestDir::new("suppl-before");
+        dir.touch("IMG_1234.jpg").touch("IMG_1234(1).jpg");
+
+        let resolved = resolve_photo_paths(
+            &dir.0,
+            "IMG_1234.jpg(1).supplemental-metadata.json",
+            "IMG_1234.jpg",
+        );
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234(1).jpg")]);
+    }
+
+    #[test]
+    fn supplemental_metadata_infix_without_counter() {
+        let dir = TestDir::new("suppl-plain");
+        dir.touch("IMG_1234.jpg");
+
+        let resolved = resolve_photo_paths(
+            &dir.0,
+            "IMG_1234.jpg.supplemental-metadata.json",
+            "IMG_1234.jpg",
+        );
+
+        assert_eq!(resolved, vec![dir.path("IMG_1234.jpg")]);
+    }
+
+    #[test]
+    fn edited_sibling_is_appended() {
+        let dir = TestDir::new("edited");
+        dir.touch("IMG_1234.jpg").touch("IMG_1234-edited.jpg");
+
+        let resolved = resolve_photo_paths(&dir.0, "IMG_1234.jpg.json", "IMG_1234.jpg");
+
+        assert_eq!(
+            resolved,
+            vec![dir.path("IMG_1234.jpg"), dir.path("IMG_1234-edited.jpg")]
+        );
+    }
+
+    #[test]
+    fn no_match_returns_empty() {
+        let dir = TestDir::new("no-match");
+
+        let resolved = resolve_photo_paths(&dir.0, "IMG_1234.jpg.json", "IMG_1234.jpg");
+
+        assert!(resolved.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+//! Watch-folder mode: instead of rescanning the whole selected directory, watch it for
+//! newly created Takeout JSONs and match just the files that showed up.
+
+use crate::{extract_metadata, fold_outcome, open_and_match, preview_match, MatchOptions, MatchReport};
+use crate::{ProgressData, ProgressMessage};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+// How long to wait after the last filesystem event before processing a batch, so a whole
+// burst of files dropped in at once (e.g. unzipping a Takeout archive) is handled together.
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Watches `path` for newly created `.json` files and matches each settled batch as it
+/// arrives, running until `stop_flag` is set. Mirrors `match_metadata_core`'s contract:
+/// every batch streams `Update`s as it's matched, and exactly one `Finished` carrying the
+/// accumulated report for the whole watch is sent once it stops.
+pub async fn watch_folder(
+    path: PathBuf,
+    options: MatchOptions,
+    stop_flag: Arc<AtomicBool>,
+    progress_sender: mpsc::Sender<ProgressMessage>,
+) {
+    let (event_sender, event_receiver) = mpsc::channel();
+
+    let mut watcher = match RecommendedWatcher::new(
+        move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                event_sender.send(event).ok();
+            }
+        },
+        notify::Config::default(),
+    ) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            send_failure(&progress_sender, format!("Failed to start watcher: {}", err));
+            return;
+        }
+    };
+
+    let recursive_mode = if options.search_subdirs {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+
+    if let Err(err) = watcher.watch(&path, recursive_mode) {
+        send_failure(&progress_sender, format!("Failed to watch {:?}: {}", path, err));
+        return;
+    }
+
+    println!("Watching {:?} for new Takeout JSONs", path);
+    let mut pending: HashSet<PathBuf> = HashSet::new();
+    let mut run_report = MatchReport::default();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        match event_receiver.recv_timeout(DEBOUNCE) {
+            Ok(event) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    pending.extend(
+                        event
+                            .paths
+                            .into_iter()
+                            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json")),
+                    );
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if !pending.is_empty() {
+                    process_batch(
+                        pending.drain().collect(),
+                        &path,
+                        &options,
+                        &mut run_report,
+                        &progress_sender,
+                    )
+                    .await;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    if !pending.is_empty() {
+        process_batch(
+            pending.drain().collect(),
+            &path,
+            &options,
+            &mut run_report,
+            &progress_sender,
+        )
+        .await;
+    }
+
+    progress_sender
+        .send(ProgressMessage::Finished(run_report))
+        .ok();
+}
+
+async fn process_batch(
+    batch: Vec<PathBuf>,
+    job_root: &PathBuf,
+    options: &MatchOptions,
+    run_report: &mut MatchReport,
+    progress_sender: &mpsc::Sender<ProgressMessage>,
+) {
+    let batch: Vec<async_std::path::PathBuf> =
+        batch.into_iter().map(async_std::path::PathBuf::from).collect();
+    let batch_total = batch.len();
+
+    let (elements, batch_extract_report) = extract_metadata(batch, job_root.clone()).await;
+    run_report.failed += batch_extract_report.failed;
+    run_report.messages.extend(batch_extract_report.messages);
+
+    for (done, element) in elements.into_iter().enumerate() {
+        let current_file = element.title.clone();
+        let json_path = element.source_dir.join(&element.json_file_name);
+
+        let (outcome, warnings) = if options.dry_run {
+            preview_match(&element, options)
+        } else {
+            open_and_match(element, options)
+        };
+        run_report.messages.extend(warnings);
+        let matched = fold_outcome(run_report, outcome);
+
+        if matched && !options.dry_run && options.delete_json {
+            if let Err(err) = std::fs::remove_file(&json_path) {
+                run_report
+                    .messages
+                    .push(format!("Failed to delete {:?}: {}", json_path, err));
+            }
+        }
+
+        progress_sender
+            .send(ProgressMessage::Update(ProgressData {
+                files_total: batch_total,
+                files_done: done + 1,
+                current_step: "Matching new Takeout files".to_string(),
+                current_file,
+            }))
+            .ok();
+    }
+}
+
+fn send_failure(progress_sender: &mpsc::Sender<ProgressMessage>, message: String) {
+    progress_sender
+        .send(ProgressMessage::Finished(MatchReport {
+            failed: 1,
+            messages: vec![message],
+            ..Default::default()
+        }))
+        .ok();
+}